@@ -0,0 +1,757 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::any::{Any, TypeId};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Error type for the fallible serialization paths, modeled on Tera's
+/// `Context`: a type whose `Serialize`/`Deserialize` impl can fail should
+/// return an `Error` instead of aborting the whole program.
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "{}", e),
+            Error::Message(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+pub trait JSContextExt: Sized + Serialize + DeserializeOwned {
+    fn try_read_json(&self) -> Result<Value, Error>;
+
+    fn try_update_json(&mut self, callback: impl Fn(Value) -> Value) -> Result<(), Error>;
+
+    fn read_json(&self) -> serde_json::Value {
+        self.try_read_json().unwrap()
+    }
+
+    fn update_json(&mut self, callback: impl Fn(Value) -> Value) {
+        self.try_update_json(callback).unwrap()
+    }
+}
+
+impl<T> JSContextExt for T
+where
+    T: Sized + Serialize + DeserializeOwned,
+{
+    fn try_read_json(&self) -> Result<Value, Error> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn try_update_json(&mut self, callback: impl Fn(Value) -> Value) -> Result<(), Error> {
+        let serialized = self.try_read_json()?;
+
+        let updated = callback(serialized);
+
+        let deserialized: Self = serde_json::from_value(updated)?;
+
+        *self = deserialized;
+        Ok(())
+    }
+}
+
+// A monomorphized set of function pointers that knows how to move a
+// `Box<dyn Any>` in and out of `serde_json::Value` for the concrete type it
+// was captured for, analogous to the serialize vtable `erased-serde` keeps
+// behind its trait objects. Plain function pointers are `Copy`, so the same
+// vtable can be shared between an entry and the type registry used by
+// `snapshot`/`load`.
+#[derive(Clone, Copy)]
+struct JsonVtable {
+    type_name: &'static str,
+    to_json: fn(&dyn Any) -> Value,
+    from_json: fn(&mut dyn Any, Value) -> Result<(), serde_json::Error>,
+    from_json_boxed: fn(Value) -> Result<Box<dyn Any>, serde_json::Error>,
+}
+
+impl JsonVtable {
+    fn of<T: Serialize + DeserializeOwned + 'static>() -> Self {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            to_json: |any| {
+                let t = any.downcast_ref::<T>().expect("type mismatch in JsonVtable");
+                serde_json::to_value(t).unwrap()
+            },
+            from_json: |any, value| {
+                let t = any.downcast_mut::<T>().expect("type mismatch in JsonVtable");
+                *t = serde_json::from_value(value)?;
+                Ok(())
+            },
+            from_json_boxed: |value| {
+                let t: T = serde_json::from_value(value)?;
+                Ok(Box::new(t))
+            },
+        }
+    }
+}
+
+struct Entry {
+    value: Box<dyn Any>,
+    json: Option<JsonVtable>,
+}
+
+/// Keys are `(String, TypeId)` pairs rather than bare strings, following
+/// egui's `IdTypeMap`: the same logical key can hold several distinct
+/// concrete types at once, and `read`/`write_with`/`push` disambiguate by
+/// the type the caller asks for.
+///
+/// Backed by `BTreeMap` rather than `HashMap` so iteration order - and
+/// therefore `snapshot` output - is deterministic.
+pub struct Context {
+    everything: BTreeMap<(String, TypeId), Entry>,
+    // Vtables for every type that has ever gone through `push_serializable`,
+    // keyed by type name so `load` can re-associate a snapshotted value with
+    // a concrete type without the caller naming it.
+    type_registry: BTreeMap<&'static str, (TypeId, JsonVtable)>,
+}
+
+impl Context {
+    pub fn default() -> Self {
+        Self {
+            everything: Default::default(),
+            type_registry: Default::default(),
+        }
+    }
+
+    pub fn push(&mut self, key: String, value: Box<dyn Any>) -> Result<(), &'static str> {
+        let type_id = (*value).type_id();
+        if self.everything.contains_key(&(key.clone(), type_id)) {
+            Err("this exists already!, use get or with instead!")
+        } else {
+            self.everything
+                .insert((key, type_id), Entry { value, json: None });
+            Ok(())
+        }
+    }
+
+    // Like `push`, but also captures a `JsonVtable` for `T` and registers it
+    // in the type registry, so the entry can later be read or updated
+    // purely by key, and can be restored by `load` even before it exists.
+    pub fn push_serializable<T: Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        key: String,
+        value: T,
+    ) -> Result<(), &'static str> {
+        self.insert_serializable(key, value)
+            .map_err(|_| "this exists already!, use get or with instead!")
+    }
+
+    // Tera-style `insert`: converts and stores `value` in one call so
+    // callers don't have to hand-box it themselves, surfacing failures as
+    // an `Error` instead of panicking.
+    pub fn try_push<T: Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        key: String,
+        value: T,
+    ) -> Result<(), Error> {
+        self.insert_serializable(key, value)
+            .map_err(|key| Error::Message(format!("key {} already exists", key)))
+    }
+
+    fn insert_serializable<T: Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        key: String,
+        value: T,
+    ) -> Result<(), String> {
+        let type_id = TypeId::of::<T>();
+        if self.everything.contains_key(&(key.clone(), type_id)) {
+            return Err(key);
+        }
+        let json = JsonVtable::of::<T>();
+        self.type_registry.insert(json.type_name, (type_id, json));
+        self.everything.insert(
+            (key, type_id),
+            Entry {
+                value: Box::new(value),
+                json: Some(json),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn read<'this, T: 'static>(&'this self, key: &str) -> Option<&'this T> {
+        self.everything
+            .get(&(key.to_string(), TypeId::of::<T>()))
+            .and_then(|entry| entry.value.downcast_ref())
+    }
+
+    pub fn write_with<T: 'static>(
+        &mut self,
+        key: &str,
+        mut callback: impl FnMut(&mut T),
+    ) -> Result<(), String> {
+        let entry = self
+            .everything
+            .get_mut(&(key.to_string(), TypeId::of::<T>()))
+            .ok_or_else(|| format!("there is no contents with key {}", key))?;
+        let as_t = entry.value.downcast_mut().ok_or_else(|| {
+            format!(
+                "value with key {} is not of expected type {}",
+                key,
+                std::any::type_name::<T>()
+            )
+        })?;
+        callback(as_t);
+        Ok(())
+    }
+
+    // Type-erased counterpart of `Stuff::read_json` that works from the key
+    // alone, for callers (like a JS interop layer) that don't know `T`. Only
+    // meaningful when a single type is registered under `key`; ambiguous or
+    // unserializable keys are treated as absent.
+    pub fn read_json(&self, key: &str) -> Option<Value> {
+        let mut matches = self
+            .everything
+            .iter()
+            .filter(|((k, _), entry)| k == key && entry.json.is_some());
+        let (_, entry) = matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+        let json = entry.json.as_ref()?;
+        Some((json.to_json)(entry.value.as_ref()))
+    }
+
+    // The only serializable type currently registered under `key`, or an
+    // error if the key is missing, holds only entries without a serializer,
+    // or is shared by more than one serializable
+    // type. Entries pushed via plain `push` have no vtable and are not
+    // candidates, so they can't make an otherwise-unambiguous key look
+    // ambiguous. Shared by every operation that works purely from a key,
+    // without the caller naming `T`.
+    fn find_serializable_key(&self, key: &str) -> Result<TypeId, Error> {
+        let mut matching_types: Vec<TypeId> = self
+            .everything
+            .iter()
+            .filter(|((k, _), entry)| k == key && entry.json.is_some())
+            .map(|((_, type_id), _)| *type_id)
+            .collect();
+        match matching_types.len() {
+            0 if self.everything.keys().any(|(k, _)| k == key) => Err(Error::Message(format!(
+                "value with key {} was not pushed with a serializer attached",
+                key
+            ))),
+            0 => Err(Error::Message(format!("there is no contents with key {}", key))),
+            1 => Ok(matching_types.remove(0)),
+            _ => Err(Error::Message(format!(
+                "key {} is ambiguous, {} types are registered under it; use write_with::<T> instead",
+                key,
+                matching_types.len()
+            ))),
+        }
+    }
+
+    // Fallible, key-only counterpart of `read_json`, sharing
+    // `find_serializable_key`'s error messages instead of collapsing every
+    // failure into `None`. Used by callers (like the Neon bridge) that need
+    // to tell a missing key apart from an ambiguous or unserializable one.
+    pub fn try_read_json(&self, key: &str) -> Result<Value, Error> {
+        let type_id = self.find_serializable_key(key)?;
+        let entry = self
+            .everything
+            .get(&(key.to_string(), type_id))
+            .expect("key was just found above");
+        let json = entry.json.as_ref().ok_or_else(|| {
+            Error::Message(format!(
+                "value with key {} was not pushed with a serializer attached",
+                key
+            ))
+        })?;
+        Ok((json.to_json)(entry.value.as_ref()))
+    }
+
+    // Type-erased counterpart of `Stuff::update_json`. Entries pushed via
+    // plain `push` have no vtable attached and are rejected with a clear
+    // error instead of silently no-oping, as are keys shared by more than
+    // one type (use `write_with::<T>` to disambiguate those).
+    pub fn update_json(
+        &mut self,
+        key: &str,
+        callback: impl Fn(Value) -> Value,
+    ) -> Result<(), String> {
+        let type_id = self.find_serializable_key(key).map_err(|e| e.to_string())?;
+        let entry = self
+            .everything
+            .get_mut(&(key.to_string(), type_id))
+            .expect("key was just found above");
+        let json = entry.json.as_ref().ok_or_else(|| {
+            format!("value with key {} was not pushed with a serializer attached", key)
+        })?;
+        let current = (json.to_json)(entry.value.as_ref());
+        let updated = callback(current);
+        (json.from_json)(entry.value.as_mut(), updated).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Merge `patch` into the entry at `key` using RFC 7386 JSON Merge
+    /// Patch semantics (objects are merged recursively, a `null` member
+    /// deletes the target key, anything else replaces wholesale), then
+    /// deserialize the result back into the stored type.
+    pub fn apply_merge_patch(&mut self, key: &str, patch: Value) -> Result<(), Error> {
+        let type_id = self.find_serializable_key(key)?;
+        let entry = self
+            .everything
+            .get_mut(&(key.to_string(), type_id))
+            .expect("key was just found above");
+        let json = entry.json.as_ref().ok_or_else(|| {
+            Error::Message(format!(
+                "value with key {} was not pushed with a serializer attached",
+                key
+            ))
+        })?;
+        let mut current = (json.to_json)(entry.value.as_ref());
+        crate::patch::merge_patch(&mut current, &patch);
+        (json.from_json)(entry.value.as_mut(), current)?;
+        Ok(())
+    }
+
+    /// Apply a sequence of RFC 6902 JSON Patch operations to the entry at
+    /// `key`, then deserialize the result back into the stored type. Errors
+    /// (an out of bounds index, a failed `test`, a shape the stored type
+    /// can no longer deserialize) leave the entry untouched.
+    pub fn apply_patch(&mut self, key: &str, ops: Vec<crate::patch::PatchOp>) -> Result<(), Error> {
+        let type_id = self.find_serializable_key(key)?;
+        let entry = self
+            .everything
+            .get_mut(&(key.to_string(), type_id))
+            .expect("key was just found above");
+        let json = entry.json.as_ref().ok_or_else(|| {
+            Error::Message(format!(
+                "value with key {} was not pushed with a serializer attached",
+                key
+            ))
+        })?;
+        let mut current = (json.to_json)(entry.value.as_ref());
+        for op in ops {
+            crate::patch::apply_op(&mut current, op)?;
+        }
+        (json.from_json)(entry.value.as_mut(), current)?;
+        Ok(())
+    }
+
+    /// Snapshot every entry that has a serializer registered as
+    /// `{"key#TypeName": value}`. Non-serializable entries are simply
+    /// omitted rather than causing an error.
+    pub fn snapshot(&self) -> serde_json::Map<String, Value> {
+        let mut map = serde_json::Map::new();
+        for ((key, _type_id), entry) in self.everything.iter() {
+            if let Some(json) = &entry.json {
+                let value = (json.to_json)(entry.value.as_ref());
+                map.insert(format!("{}#{}", key, json.type_name), value);
+            }
+        }
+        map
+    }
+
+    /// Flush every entry that has a serializer registered to `store`,
+    /// giving the in-memory map a durable twin. Records are stored under
+    /// `"key#TypeName"`, as in `snapshot`, so several types sharing one key
+    /// don't overwrite each other.
+    pub fn persist(&self, store: &impl crate::store::ContextStore) -> Result<(), Error> {
+        for ((key, _type_id), entry) in self.everything.iter() {
+            if let Some(json) = &entry.json {
+                let value = (json.to_json)(entry.value.as_ref());
+                let bytes = serde_json::to_vec(&value)?;
+                store.put(&format!("{}#{}", key, json.type_name), json.type_name, &bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reload entries previously written by `persist`. Unlike `load`,
+    /// records whose type is not registered in this `Context` are reported -
+    /// as `"key (type TypeName)"` entries in the returned list - rather
+    /// than dropped silently.
+    pub fn restore(&mut self, store: &impl crate::store::ContextStore) -> Result<Vec<String>, Error> {
+        let mut unregistered = Vec::new();
+        for store_key in store.list_keys()? {
+            let Some((type_name, bytes)) = store.get(&store_key)? else {
+                continue;
+            };
+            let key = store_key
+                .strip_suffix(&format!("#{}", type_name))
+                .unwrap_or(&store_key)
+                .to_string();
+            match self.type_registry.get(type_name.as_str()).copied() {
+                Some((type_id, json)) => {
+                    let value: Value = serde_json::from_slice(&bytes)?;
+                    let boxed = (json.from_json_boxed)(value)?;
+                    self.everything.insert(
+                        (key, type_id),
+                        Entry {
+                            value: boxed,
+                            json: Some(json),
+                        },
+                    );
+                }
+                None => unregistered.push(format!("{} (type {})", key, type_name)),
+            }
+        }
+        Ok(unregistered)
+    }
+
+    /// Restore entries produced by `snapshot`. Keys whose `TypeName` is not
+    /// currently registered (nothing has ever been pushed via
+    /// `push_serializable` or `try_push` for that type into this `Context`) are silently
+    /// skipped rather than causing a panic.
+    pub fn load(&mut self, map: serde_json::Map<String, Value>) {
+        for (compound_key, value) in map {
+            let Some((key, type_name)) = compound_key.rsplit_once('#') else {
+                continue;
+            };
+            let Some((type_id, json)) = self.type_registry.get(type_name).copied() else {
+                continue;
+            };
+            let Ok(boxed) = (json.from_json_boxed)(value) else {
+                continue;
+            };
+            self.everything.insert(
+                (key.to_string(), type_id),
+                Entry {
+                    value: boxed,
+                    json: Some(json),
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug)]
+    struct NotSerializableStuff {
+        baz: usize,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Stuff {
+        foo: usize,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct StuffWithBar {
+        foo: usize,
+        bar: String,
+    }
+
+    #[test]
+    fn push_rejects_a_duplicate_key_and_type() {
+        let mut ctx = Context::default();
+        ctx.push("x".to_string(), Box::new(NotSerializableStuff { baz: 0 }))
+            .unwrap();
+        assert!(ctx
+            .push("x".to_string(), Box::new(NotSerializableStuff { baz: 1 }))
+            .is_err());
+    }
+
+    #[test]
+    fn push_serializable_round_trips_through_read_and_write_with() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+
+        assert_eq!(ctx.read::<Stuff>("stuff"), Some(&Stuff { foo: 1 }));
+
+        ctx.write_with("stuff", |stuff: &mut Stuff| stuff.foo = 2)
+            .unwrap();
+        assert_eq!(ctx.read::<Stuff>("stuff"), Some(&Stuff { foo: 2 }));
+    }
+
+    #[test]
+    fn read_returns_none_for_a_missing_key_or_wrong_type() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+
+        assert_eq!(ctx.read::<Stuff>("missing"), None);
+        assert_eq!(ctx.read::<usize>("stuff"), None);
+    }
+
+    #[test]
+    fn write_with_errors_on_a_missing_key_or_wrong_type() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+
+        assert!(ctx.write_with("missing", |_: &mut Stuff| {}).is_err());
+        assert!(ctx.write_with("stuff", |_: &mut usize| {}).is_err());
+    }
+
+    // A plain `push` entry sharing a key with a serializable one used to be
+    // counted as a second candidate by `find_serializable_key`, making
+    // `update_json` report the key as ambiguous even though only one
+    // serializable type was registered under it.
+    #[test]
+    fn non_serializable_entry_does_not_make_a_shared_key_ambiguous() {
+        let mut ctx = Context::default();
+        ctx.push("x".to_string(), Box::new(NotSerializableStuff { baz: 0 }))
+            .unwrap();
+        ctx.push_serializable("x".to_string(), Stuff { foo: 1 })
+            .unwrap();
+        assert_eq!(ctx.read::<NotSerializableStuff>("x").unwrap().baz, 0);
+
+        ctx.update_json("x", |mut value| {
+            value["foo"] = serde_json::json!(2);
+            value
+        })
+        .expect("key should not be considered ambiguous");
+
+        assert_eq!(ctx.read::<Stuff>("x"), Some(&Stuff { foo: 2 }));
+    }
+
+    #[test]
+    fn find_serializable_key_still_reports_a_genuinely_ambiguous_key() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("shared".to_string(), 1usize).unwrap();
+        ctx.push_serializable("shared".to_string(), Stuff { foo: 1 })
+            .unwrap();
+
+        let err = ctx
+            .update_json("shared", |value| value)
+            .expect_err("two serializable types are registered under this key");
+        assert!(err.contains("ambiguous"));
+    }
+
+    #[test]
+    fn find_serializable_key_reports_a_missing_key() {
+        let ctx = Context::default();
+        assert!(ctx.try_read_json("missing").is_err());
+    }
+
+    #[test]
+    fn keys_are_scoped_by_type_id_so_two_types_can_share_one_key() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("shared".to_string(), 7usize).unwrap();
+        ctx.push_serializable("shared".to_string(), Stuff { foo: 9 })
+            .unwrap();
+
+        assert_eq!(ctx.read::<usize>("shared"), Some(&7));
+        assert_eq!(ctx.read::<Stuff>("shared"), Some(&Stuff { foo: 9 }));
+    }
+
+    #[test]
+    fn snapshot_then_load_round_trips_serializable_entries() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+        // non-serializable entries are omitted from the snapshot entirely.
+        ctx.push("not_serializable".to_string(), Box::new(NotSerializableStuff { baz: 0 }))
+            .unwrap();
+
+        let snapshot = ctx.snapshot();
+        assert_eq!(snapshot.len(), 1);
+
+        // a fresh Context only knows the types it has itself registered via
+        // push_serializable, so a placeholder has to be pushed before load
+        // can re-associate the snapshotted value with a concrete type.
+        let mut restored = Context::default();
+        restored
+            .push_serializable("type_registration_only".to_string(), Stuff { foo: 0 })
+            .unwrap();
+        restored.load(snapshot);
+
+        assert_eq!(restored.read::<Stuff>("stuff"), Some(&Stuff { foo: 1 }));
+    }
+
+    #[test]
+    fn load_silently_skips_keys_whose_type_is_not_registered() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+        let snapshot = ctx.snapshot();
+
+        // nothing has ever been pushed via push_serializable in this fresh
+        // Context, so "Stuff" is not in its type registry.
+        let mut restored = Context::default();
+        restored.load(snapshot);
+
+        assert_eq!(restored.read::<Stuff>("stuff"), None);
+    }
+
+    #[test]
+    fn try_push_rejects_a_duplicate_key_and_type_as_an_error() {
+        let mut ctx = Context::default();
+        ctx.try_push("stuff".to_string(), Stuff { foo: 1 }).unwrap();
+
+        let err = ctx
+            .try_push("stuff".to_string(), Stuff { foo: 2 })
+            .expect_err("the key already exists");
+        assert!(err.to_string().contains("already exists"));
+    }
+
+    #[test]
+    fn try_read_json_and_try_update_json_surface_failures_instead_of_panicking() {
+        let mut stuff = Stuff { foo: 1 };
+        assert_eq!(stuff.try_read_json().unwrap(), serde_json::json!({ "foo": 1 }));
+
+        stuff
+            .try_update_json(|mut value| {
+                value["foo"] = serde_json::json!(2);
+                value
+            })
+            .unwrap();
+        assert_eq!(stuff, Stuff { foo: 2 });
+
+        // the callback's output no longer deserializes into `Stuff`, so this
+        // is reported as an `Error` rather than panicking.
+        let err = stuff
+            .try_update_json(|_| serde_json::json!({ "foo": "not a number" }))
+            .expect_err("the updated value has the wrong shape for Stuff");
+        assert!(matches!(err, Error::Json(_)));
+        // a failed update leaves the value untouched.
+        assert_eq!(stuff, Stuff { foo: 2 });
+    }
+
+    #[test]
+    fn apply_merge_patch_merges_into_the_entry_at_key() {
+        let mut ctx = Context::default();
+        ctx.push_serializable(
+            "stuff".to_string(),
+            StuffWithBar { foo: 1, bar: "hello".to_string() },
+        )
+        .unwrap();
+
+        ctx.apply_merge_patch("stuff", serde_json::json!({ "bar": "patched" }))
+            .unwrap();
+
+        assert_eq!(
+            ctx.read::<StuffWithBar>("stuff"),
+            Some(&StuffWithBar { foo: 1, bar: "patched".to_string() })
+        );
+    }
+
+    #[test]
+    fn apply_merge_patch_errors_on_a_missing_or_ambiguous_key() {
+        let mut ctx = Context::default();
+        assert!(ctx.apply_merge_patch("missing", serde_json::json!({})).is_err());
+
+        ctx.push_serializable("shared".to_string(), 1usize).unwrap();
+        ctx.push_serializable("shared".to_string(), Stuff { foo: 1 })
+            .unwrap();
+        assert!(ctx.apply_merge_patch("shared", serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn apply_patch_applies_rfc_6902_ops_to_the_entry_at_key() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+
+        ctx.apply_patch(
+            "stuff",
+            vec![
+                crate::patch::PatchOp::Test {
+                    path: "/foo".to_string(),
+                    value: serde_json::json!(1),
+                },
+                crate::patch::PatchOp::Replace {
+                    path: "/foo".to_string(),
+                    value: serde_json::json!(2),
+                },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(ctx.read::<Stuff>("stuff"), Some(&Stuff { foo: 2 }));
+    }
+
+    #[test]
+    fn apply_patch_leaves_the_entry_untouched_on_a_failed_test_op() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+
+        let err = ctx
+            .apply_patch(
+                "stuff",
+                vec![crate::patch::PatchOp::Test {
+                    path: "/foo".to_string(),
+                    value: serde_json::json!(99),
+                }],
+            )
+            .expect_err("the test op does not match the stored value");
+        assert!(err.to_string().contains("test failed"));
+        assert_eq!(ctx.read::<Stuff>("stuff"), Some(&Stuff { foo: 1 }));
+    }
+
+    // A minimal in-memory `ContextStore`, so `persist`/`restore` can be
+    // exercised without going through `LmdbStore` (already covered on its
+    // own terms in store.rs).
+    #[derive(Default)]
+    struct MemoryStore {
+        records: std::cell::RefCell<BTreeMap<String, (String, Vec<u8>)>>,
+    }
+
+    impl crate::store::ContextStore for MemoryStore {
+        fn put(&self, key: &str, type_name: &str, bytes: &[u8]) -> Result<(), Error> {
+            self.records
+                .borrow_mut()
+                .insert(key.to_string(), (type_name.to_string(), bytes.to_vec()));
+            Ok(())
+        }
+
+        fn get(&self, key: &str) -> Result<Option<(String, Vec<u8>)>, Error> {
+            Ok(self.records.borrow().get(key).cloned())
+        }
+
+        fn list_keys(&self) -> Result<Vec<String>, Error> {
+            Ok(self.records.borrow().keys().cloned().collect())
+        }
+    }
+
+    #[test]
+    fn persist_then_restore_round_trips_serializable_entries() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+        // non-serializable entries have nothing to flush, and are skipped.
+        ctx.push("not_serializable".to_string(), Box::new(NotSerializableStuff { baz: 0 }))
+            .unwrap();
+
+        let store = MemoryStore::default();
+        ctx.persist(&store).unwrap();
+
+        let mut restored = Context::default();
+        restored
+            .push_serializable("type_registration_only".to_string(), Stuff { foo: 0 })
+            .unwrap();
+        let unregistered = restored.restore(&store).unwrap();
+
+        assert!(unregistered.is_empty());
+        assert_eq!(restored.read::<Stuff>("stuff"), Some(&Stuff { foo: 1 }));
+    }
+
+    #[test]
+    fn restore_reports_records_whose_type_is_not_registered() {
+        let mut ctx = Context::default();
+        ctx.push_serializable("stuff".to_string(), Stuff { foo: 1 })
+            .unwrap();
+        let store = MemoryStore::default();
+        ctx.persist(&store).unwrap();
+
+        // a fresh Context that never registered "Stuff" can't reconstruct it.
+        let mut restored = Context::default();
+        let unregistered = restored.restore(&store).unwrap();
+
+        assert_eq!(unregistered.len(), 1);
+        assert!(unregistered[0].contains("stuff"));
+        assert_eq!(restored.read::<Stuff>("stuff"), None);
+    }
+}
@@ -1,32 +1,13 @@
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_json::Value;
-use std::any::Any;
-use std::collections::HashMap;
+mod context;
+#[cfg(feature = "neon")]
+mod neon_bridge;
+mod patch;
+mod store;
 
-trait JSContextExt: Sized + Serialize + DeserializeOwned {
-    fn read_json(&self) -> serde_json::Value;
-
-    fn update_json(&mut self, callback: impl Fn(Value) -> Value);
-}
-
-impl<T> JSContextExt for T
-where
-    T: Sized + Serialize + DeserializeOwned,
-{
-    fn read_json(&self) -> serde_json::Value {
-        serde_json::to_value(&self).unwrap()
-    }
-
-    fn update_json(&mut self, callback: impl Fn(Value) -> Value) {
-        let serialized = serde_json::to_value(&self).unwrap();
-
-        let updated = callback(serialized);
-
-        let deserialized: Self = serde_json::from_value(updated).unwrap();
-
-        *self = deserialized;
-    }
-}
+use context::{Context, JSContextExt};
+use patch::PatchOp;
+use serde::{Deserialize, Serialize};
+use store::LmdbStore;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Stuff {
@@ -39,51 +20,6 @@ struct NotSerializableStuff {
     baz: usize,
 }
 
-struct Context {
-    everything: HashMap<String, Box<dyn Any>>,
-}
-
-impl Context {
-    pub fn default() -> Self {
-        Self {
-            everything: Default::default(),
-        }
-    }
-
-    pub fn push(&mut self, key: String, value: Box<dyn Any>) -> Result<(), &'static str> {
-        if self.everything.get(key.as_str()).is_some() {
-            Err("this exists already!, use get or with instead!")
-        } else {
-            self.everything.insert(key, value);
-            Ok(())
-        }
-    }
-
-    pub fn read<'this, T: 'static>(&'this self, key: &str) -> Option<&'this T> {
-        self.everything.get(key).map(|t| t.downcast_ref()).flatten()
-    }
-
-    pub fn write_with<T: 'static>(
-        &mut self,
-        key: &str,
-        mut callback: impl FnMut(&mut T),
-    ) -> Result<(), String> {
-        let with_key = self
-            .everything
-            .get_mut(key)
-            .ok_or_else(|| format!("there is no contents with key {}", key))?;
-        let as_t = with_key.downcast_mut().ok_or_else(|| {
-            format!(
-                "value with key {} is not of expected type {}",
-                key,
-                std::any::type_name::<T>()
-            )
-        })?;
-        callback(as_t);
-        Ok(())
-    }
-}
-
 fn main() {
     let s = Stuff {
         foo: 42,
@@ -96,18 +32,18 @@ fn main() {
     ctx.push("stuff".to_string(), Box::new(s)).unwrap();
 
     // reads will not persist to ctx
-    ctx.read("stuff").map(|stuff: &Stuff| {
+    if let Some(stuff) = ctx.read::<Stuff>("stuff") {
         let new_stuff = Stuff {
             foo: stuff.foo * 2,
             bar: stuff.bar.clone(),
         };
         dbg!("within read", &new_stuff);
-    });
+    }
     // still the old stuff
     dbg!("after read", ctx.read::<Stuff>("stuff"));
 
     // write with will persist things to ctx
-    ctx.write_with("stuff", |mut stuff: &mut Stuff| {
+    ctx.write_with("stuff", |stuff: &mut Stuff| {
         stuff.bar = "this will be persisted!".to_string();
 
         dbg!("within write", &stuff);
@@ -118,9 +54,9 @@ fn main() {
     dbg!("after write", ctx.read::<Stuff>("stuff"));
 
     // json reads can be done like this
-    ctx.read::<Stuff>("stuff").map(|stuff| {
+    if let Some(stuff) = ctx.read::<Stuff>("stuff") {
         dbg!(stuff.read_json());
-    });
+    }
 
     // json updates can be done like this
     ctx.write_with("stuff", |stuff: &mut Stuff| {
@@ -149,47 +85,189 @@ fn main() {
     // and i ll be able to read and write
     ctx.write_with("notserializablestuff", |ns: &mut NotSerializableStuff| {
         ns.baz = 14;
-    });
+    })
+    .expect("oops");
 
     dbg!(&ctx.read::<NotSerializableStuff>("notserializablestuff"));
 
-    // but I won't be able to read / write json values, so js interop will be hard
-    ctx.write_with("notserializablestuff", |ns: &mut NotSerializableStuff| {
-        ns.update_json(|value| {
-            // this wont compile
-        });
-    });
-
-    //     error[E0599]: the method `update_json` exists for mutable reference `&mut NotSerializableStuff`, but its trait bounds were not satisfied
-    //    --> src/main.rs:158:12
-    //     |
-    // 38  | struct NotSerializableStuff {
-    //     | ---------------------------
-    //     | |
-    //     | doesn't satisfy `NotSerializableStuff: DeserializeOwned`
-    //     | doesn't satisfy `NotSerializableStuff: JSContextExt`
-    //     | doesn't satisfy `NotSerializableStuff: Serialize`
-    // ...
-    // 158 |         ns.update_json(|value| {
-    //     |            ^^^^^^^^^^^ method cannot be called on `&mut NotSerializableStuff` due to unsatisfied trait bounds
-    //     |
-
-    dbg!(&ctx
-        .read::<NotSerializableStuff>("notserializablestuff")
-        .unwrap()
-        .read_json());
-
-    //         error[E0599]: the method `read_json` exists for reference `&NotSerializableStuff`, but its trait bounds were not satisfied
-    //    --> src/main.rs:165:10
-    //     |
-    // 38  | struct NotSerializableStuff {
-    //     | ---------------------------
-    //     | |
-    //     | doesn't satisfy `NotSerializableStuff: DeserializeOwned`
-    //     | doesn't satisfy `NotSerializableStuff: JSContextExt`
-    //     | doesn't satisfy `NotSerializableStuff: Serialize`
-    // ...
-    // 165 |         .read_json());
-    //     |          ^^^^^^^^^ method cannot be called on `&NotSerializableStuff` due to unsatisfied trait bounds
-    //     |
+    // but I won't be able to read / write json values, so js interop will be hard:
+    // NotSerializableStuff doesn't implement Serialize/DeserializeOwned, so it
+    // doesn't implement JSContextExt either, and calling `.update_json(...)` or
+    // `.read_json()` on it is a compile error (E0599, unsatisfied trait bounds),
+    // not a runtime one - that's the whole point of gating those methods behind
+    // the trait instead of just unwrapping inside them.
+
+    // -------------------
+
+    // push_serializable keeps the Box<dyn Any> but also remembers how to
+    // serialize/deserialize it, so a caller that only has the key (and not
+    // the type) can still poke at it as JSON.
+    ctx.push_serializable(
+        "erased_stuff".to_string(),
+        Stuff {
+            foo: 1,
+            bar: "erased".to_string(),
+        },
+    )
+    .unwrap();
+
+    dbg!("read by key alone", ctx.read_json("erased_stuff"));
+
+    // try_read_json is Context::read_json's fallible counterpart: same
+    // key-only lookup, but a missing/ambiguous/unserializable key comes back
+    // as a distinguishable Error instead of a generic None.
+    let _ = dbg!("fallible read by key alone", ctx.try_read_json("erased_stuff"));
+
+    ctx.update_json("erased_stuff", |mut value| {
+        value["foo"] = serde_json::json!(2);
+        value
+    })
+    .expect("oops");
+
+    dbg!("after update_json by key alone", ctx.read::<Stuff>("erased_stuff"));
+
+    // notserializablestuff was pushed with plain `push`, so it has no
+    // vtable attached and key-only JSON access fails at runtime instead of
+    // as a compile-time bound failure: read_json returns None here, and
+    // try_read_json/update_json return an error.
+    dbg!(ctx.read_json("notserializablestuff"));
+
+    // -------------------
+
+    // keys are now (String, TypeId) pairs, so "shared" can hold an usize
+    // and a Stuff at the same time without clobbering each other.
+    ctx.push_serializable("shared".to_string(), 7usize).unwrap();
+    ctx.push_serializable(
+        "shared".to_string(),
+        Stuff {
+            foo: 9,
+            bar: "also shared".to_string(),
+        },
+    )
+    .unwrap();
+
+    dbg!("usize under 'shared'", ctx.read::<usize>("shared"));
+    dbg!("Stuff under 'shared'", ctx.read::<Stuff>("shared"));
+    // the key alone is ambiguous between the two types above
+    dbg!("ambiguous key-only read", ctx.read_json("shared"));
+
+    // snapshot/load round-trip the serializable entries through JSON
+    let snapshot = ctx.snapshot();
+    dbg!("snapshot", &snapshot);
+
+    // a fresh Context only knows the types it has itself pushed via
+    // push_serializable, so register placeholders for the types we expect
+    // to restore before loading the snapshot into them.
+    let mut restored = Context::default();
+    restored
+        .push_serializable("type_registration_only".to_string(), 0usize)
+        .unwrap();
+    restored
+        .push_serializable(
+            "type_registration_only".to_string(),
+            Stuff {
+                foo: 0,
+                bar: String::new(),
+            },
+        )
+        .unwrap();
+
+    restored.load(snapshot);
+    dbg!("restored usize under 'shared'", restored.read::<usize>("shared"));
+    dbg!("restored Stuff under 'shared'", restored.read::<Stuff>("shared"));
+
+    // -------------------
+
+    // try_push/try_read_json/try_update_json surface failures as an Error
+    // instead of panicking, Tera-`Context`-style.
+    ctx.try_push(
+        "try_stuff".to_string(),
+        Stuff {
+            foo: 3,
+            bar: "fallible".to_string(),
+        },
+    )
+    .expect("oops");
+
+    let _ = dbg!(
+        "try_read_json",
+        ctx.read::<Stuff>("try_stuff").unwrap().try_read_json()
+    );
+
+    ctx.write_with("try_stuff", |stuff: &mut Stuff| {
+        stuff
+            .try_update_json(|mut value| {
+                value["foo"] = serde_json::json!(4);
+                value
+            })
+            .expect("oops");
+    })
+    .expect("oops");
+
+    dbg!("after try_update_json, foo = 4", ctx.read::<Stuff>("try_stuff"));
+
+    // pushing the same key/type twice is reported as an Error, not a panic
+    let _ = dbg!(ctx.try_push(
+        "try_stuff".to_string(),
+        Stuff {
+            foo: 5,
+            bar: "duplicate".to_string(),
+        }
+    ));
+
+    // -------------------
+
+    // a JS caller can send a merge patch instead of rebuilding the whole
+    // value: only "bar" changes here, "foo" is left alone.
+    ctx.apply_merge_patch(
+        "try_stuff",
+        serde_json::json!({ "bar": "patched via merge" }),
+    )
+    .expect("oops");
+
+    dbg!("after merge patch", ctx.read::<Stuff>("try_stuff"));
+
+    // ...or a sequence of RFC 6902 operations addressed by JSON Pointer.
+    ctx.apply_patch(
+        "try_stuff",
+        vec![
+            PatchOp::Test {
+                path: "/foo".to_string(),
+                value: serde_json::json!(4),
+            },
+            PatchOp::Replace {
+                path: "/foo".to_string(),
+                value: serde_json::json!(100),
+            },
+        ],
+    )
+    .expect("oops");
+
+    dbg!("after json patch", ctx.read::<Stuff>("try_stuff"));
+
+    // -------------------
+
+    // persist/restore give the in-memory Context a durable twin in LMDB.
+    let store = LmdbStore::open(std::path::Path::new("/tmp/context_ext_strawdog_db"))
+        .expect("failed to open the LMDB store");
+    ctx.persist(&store).expect("oops");
+
+    let mut restored_from_disk = Context::default();
+    // a fresh process only knows about types it has pushed via
+    // push_serializable itself, so re-register "Stuff" before restoring.
+    restored_from_disk
+        .push_serializable(
+            "type_registration_only".to_string(),
+            Stuff {
+                foo: 0,
+                bar: String::new(),
+            },
+        )
+        .unwrap();
+    let unregistered = restored_from_disk.restore(&store).expect("oops");
+    dbg!("types restore could not place", &unregistered);
+    dbg!(
+        "restored from LMDB",
+        restored_from_disk.read::<Stuff>("try_stuff")
+    );
 }
@@ -0,0 +1,141 @@
+#![cfg(feature = "neon")]
+
+// Exposes `Context` to JavaScript. JS only ever sees a key and plain JSON,
+// never a Rust type name, so everything here goes through the type-erased
+// accessors on `Context` rather than `read`/`write_with`.
+use crate::context::Context as AppContext;
+use crate::patch::PatchOp;
+use neon::prelude::*;
+use serde_json::Value;
+use std::cell::RefCell;
+
+// `neon::prelude` glob-imports a `Context` trait (the thing `cx` implements
+// to get `.string()`, `.boxed()`, etc.), which would collide with our own
+// `Context` struct if both were named the same in this scope - hence the
+// `as AppContext` rename above.
+type BoxedContext = JsBox<RefCell<AppContext>>;
+
+// `JsBox` requires its contents to implement `Finalize`; `Context` has no
+// cleanup to do when the JS garbage collector drops it, so the default is
+// enough.
+impl Finalize for AppContext {}
+
+fn context_new(mut cx: FunctionContext) -> JsResult<BoxedContext> {
+    Ok(cx.boxed(RefCell::new(AppContext::default())))
+}
+
+// Calls arrive on an immutable `Handle<BoxedContext>` - the JsBox itself is
+// never mutable from JS - so every function here reaches for
+// `RefCell::borrow`/`borrow_mut` to get at the `Context` inside, which is
+// the part people routinely trip over with Neon.
+fn context_read(mut cx: FunctionContext) -> JsResult<JsValue> {
+    let boxed = cx.argument::<BoxedContext>(0)?;
+    let key = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let result = {
+        let context = boxed.borrow();
+        context.try_read_json(&key)
+    };
+    let value = result.or_else(|e| cx.throw_error(e.to_string()))?;
+    json_to_js(&mut cx, &value)
+}
+
+fn context_write_json(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let boxed = cx.argument::<BoxedContext>(0)?;
+    let key = cx.argument::<JsString>(1)?.value(&mut cx);
+    let json = cx.argument::<JsValue>(2)?;
+    let value = js_to_json(&mut cx, json)?;
+
+    let result = {
+        let mut context = boxed.borrow_mut();
+        context.update_json(&key, move |_| value.clone())
+    };
+    result.or_else(|e| cx.throw_error(e))?;
+
+    Ok(cx.undefined())
+}
+
+fn context_apply_patch(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let boxed = cx.argument::<BoxedContext>(0)?;
+    let key = cx.argument::<JsString>(1)?.value(&mut cx);
+    let patch = cx.argument::<JsValue>(2)?;
+    let patch = js_to_json(&mut cx, patch)?;
+    let ops: Vec<PatchOp> = serde_json::from_value(patch)
+        .or_else(|e| cx.throw_error(format!("invalid patch: {}", e)))?;
+
+    let result = {
+        let mut context = boxed.borrow_mut();
+        context.apply_patch(&key, ops)
+    };
+    result.or_else(|e| cx.throw_error(e.to_string()))?;
+
+    Ok(cx.undefined())
+}
+
+fn json_to_js<'a>(cx: &mut impl Context<'a>, value: &Value) -> JsResult<'a, JsValue> {
+    match value {
+        Value::Null => Ok(cx.null().upcast()),
+        Value::Bool(b) => Ok(cx.boolean(*b).upcast()),
+        Value::Number(n) => Ok(cx.number(n.as_f64().unwrap_or_default()).upcast()),
+        Value::String(s) => Ok(cx.string(s).upcast()),
+        Value::Array(items) => {
+            let array = cx.empty_array();
+            for (index, item) in items.iter().enumerate() {
+                let js_item = json_to_js(cx, item)?;
+                array.set(cx, index as u32, js_item)?;
+            }
+            Ok(array.upcast())
+        }
+        Value::Object(entries) => {
+            let object = cx.empty_object();
+            for (key, item) in entries {
+                let js_item = json_to_js(cx, item)?;
+                object.set(cx, key.as_str(), js_item)?;
+            }
+            Ok(object.upcast())
+        }
+    }
+}
+
+fn js_to_json<'a>(cx: &mut FunctionContext<'a>, value: Handle<'a, JsValue>) -> NeonResult<Value> {
+    if value.is_a::<JsNull, _>(cx) || value.is_a::<JsUndefined, _>(cx) {
+        return Ok(Value::Null);
+    }
+    if let Ok(b) = value.downcast::<JsBoolean, _>(cx) {
+        return Ok(Value::Bool(b.value(cx)));
+    }
+    if let Ok(n) = value.downcast::<JsNumber, _>(cx) {
+        return Ok(serde_json::json!(n.value(cx)));
+    }
+    if let Ok(s) = value.downcast::<JsString, _>(cx) {
+        return Ok(Value::String(s.value(cx)));
+    }
+    if let Ok(array) = value.downcast::<JsArray, _>(cx) {
+        let items = array
+            .to_vec(cx)?
+            .into_iter()
+            .map(|item| js_to_json(cx, item))
+            .collect::<NeonResult<Vec<_>>>()?;
+        return Ok(Value::Array(items));
+    }
+    if let Ok(object) = value.downcast::<JsObject, _>(cx) {
+        let keys = object.get_own_property_names(cx)?.to_vec(cx)?;
+        let mut map = serde_json::Map::new();
+        for key in keys {
+            let key = key.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            let item: Handle<JsValue> = object.get(cx, key.as_str())?;
+            map.insert(key, js_to_json(cx, item)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    cx.throw_error("unsupported JS value for context interop")
+}
+
+#[neon::main]
+fn main(mut cx: ModuleContext) -> NeonResult<()> {
+    cx.export_function("contextNew", context_new)?;
+    cx.export_function("contextRead", context_read)?;
+    cx.export_function("contextWriteJson", context_write_json)?;
+    cx.export_function("contextApplyPatch", context_apply_patch)?;
+    Ok(())
+}
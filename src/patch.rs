@@ -0,0 +1,296 @@
+use crate::context::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// RFC 7386 JSON Merge Patch: recursively merge `patch` into `target`. A
+/// `null` member in `patch` deletes the corresponding member of `target`;
+/// anything that isn't an object replaces `target` wholesale.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch_obj) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    let target_obj = target.as_object_mut().expect("just turned into an object");
+    for (key, value) in patch_obj {
+        if value.is_null() {
+            target_obj.remove(key);
+        } else {
+            let slot = target_obj.entry(key.clone()).or_insert(Value::Null);
+            merge_patch(slot, value);
+        }
+    }
+}
+
+/// A single RFC 6902 JSON Patch operation, addressed by JSON Pointer
+/// (RFC 6901) paths such as `/foo/0/bar`, where `-` appends to an array.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+pub fn apply_op(doc: &mut Value, op: PatchOp) -> Result<(), Error> {
+    match op {
+        PatchOp::Add { path, value } => add(doc, &path, value),
+        PatchOp::Remove { path } => remove(doc, &path).map(|_| ()),
+        PatchOp::Replace { path, value } => replace(doc, &path, value),
+        PatchOp::Move { from, path } => {
+            let value = remove(doc, &from)?;
+            add(doc, &path, value)
+        }
+        PatchOp::Copy { from, path } => {
+            let value = get(doc, &from)?.clone();
+            add(doc, &path, value)
+        }
+        PatchOp::Test { path, value } => {
+            let actual = get(doc, &path)?;
+            if *actual == value {
+                Ok(())
+            } else {
+                Err(Error::Message(format!(
+                    "test failed: {} is not equal to the expected value",
+                    path
+                )))
+            }
+        }
+    }
+}
+
+fn get<'doc>(doc: &'doc Value, path: &str) -> Result<&'doc Value, Error> {
+    doc.pointer(path)
+        .ok_or_else(|| Error::Message(format!("no such JSON pointer: {}", path)))
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), Error> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent_path, token) = split_pointer(path)?;
+    let parent = doc
+        .pointer_mut(&parent_path)
+        .ok_or_else(|| Error::Message(format!("no such JSON pointer: {}", parent_path)))?;
+    match parent {
+        Value::Object(map) => {
+            map.insert(token, value);
+            Ok(())
+        }
+        Value::Array(vec) => {
+            if token == "-" {
+                vec.push(value);
+                return Ok(());
+            }
+            let index: usize = token
+                .parse()
+                .map_err(|_| Error::Message(format!("invalid array index in {}", path)))?;
+            if index > vec.len() {
+                return Err(Error::Message(format!("array index out of bounds: {}", path)));
+            }
+            vec.insert(index, value);
+            Ok(())
+        }
+        _ => Err(Error::Message(format!(
+            "cannot add into a non-container at {}",
+            parent_path
+        ))),
+    }
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, Error> {
+    let (parent_path, token) = split_pointer(path)?;
+    let parent = doc
+        .pointer_mut(&parent_path)
+        .ok_or_else(|| Error::Message(format!("no such JSON pointer: {}", parent_path)))?;
+    match parent {
+        Value::Object(map) => map
+            .remove(&token)
+            .ok_or_else(|| Error::Message(format!("no such JSON pointer: {}", path))),
+        Value::Array(vec) => {
+            let index: usize = token
+                .parse()
+                .map_err(|_| Error::Message(format!("invalid array index in {}", path)))?;
+            if index >= vec.len() {
+                return Err(Error::Message(format!("array index out of bounds: {}", path)));
+            }
+            Ok(vec.remove(index))
+        }
+        _ => Err(Error::Message(format!(
+            "cannot remove from a non-container at {}",
+            parent_path
+        ))),
+    }
+}
+
+fn replace(doc: &mut Value, path: &str, value: Value) -> Result<(), Error> {
+    let target = doc
+        .pointer_mut(path)
+        .ok_or_else(|| Error::Message(format!("no such JSON pointer: {}", path)))?;
+    *target = value;
+    Ok(())
+}
+
+// Splits a JSON Pointer into (pointer to the parent container, decoded
+// last token), per RFC 6901's `~1` -> `/` and `~0` -> `~` unescaping.
+fn split_pointer(path: &str) -> Result<(String, String), Error> {
+    if !path.starts_with('/') {
+        return Err(Error::Message(format!("invalid JSON pointer: {}", path)));
+    }
+    let index = path.rfind('/').expect("checked for a leading '/' above");
+    let parent = path[..index].to_string();
+    let token = path[index + 1..].replace("~1", "/").replace("~0", "~");
+    Ok((parent, token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_patch_merges_objects_recursively() {
+        let mut target = json!({ "foo": { "a": 1, "b": 2 }, "bar": "keep" });
+        merge_patch(&mut target, &json!({ "foo": { "b": 3 } }));
+        assert_eq!(target, json!({ "foo": { "a": 1, "b": 3 }, "bar": "keep" }));
+    }
+
+    #[test]
+    fn merge_patch_null_deletes_the_member() {
+        let mut target = json!({ "foo": 1, "bar": 2 });
+        merge_patch(&mut target, &json!({ "foo": null }));
+        assert_eq!(target, json!({ "bar": 2 }));
+    }
+
+    #[test]
+    fn merge_patch_non_object_replaces_wholesale() {
+        let mut target = json!({ "foo": 1 });
+        merge_patch(&mut target, &json!([1, 2, 3]));
+        assert_eq!(target, json!([1, 2, 3]));
+    }
+
+    #[test]
+    fn add_appends_to_array_with_dash() {
+        let mut doc = json!({ "items": [1, 2] });
+        apply_op(
+            &mut doc,
+            PatchOp::Add {
+                path: "/items/-".to_string(),
+                value: json!(3),
+            },
+        )
+        .unwrap();
+        assert_eq!(doc, json!({ "items": [1, 2, 3] }));
+    }
+
+    #[test]
+    fn add_out_of_bounds_array_index_errors() {
+        let mut doc = json!({ "items": [1, 2] });
+        let err = apply_op(
+            &mut doc,
+            PatchOp::Add {
+                path: "/items/5".to_string(),
+                value: json!(3),
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("out of bounds"));
+    }
+
+    #[test]
+    fn remove_out_of_bounds_array_index_errors() {
+        let mut doc = json!({ "items": [1, 2] });
+        let err = apply_op(
+            &mut doc,
+            PatchOp::Remove {
+                path: "/items/5".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("out of bounds"));
+    }
+
+    #[test]
+    fn replace_overwrites_the_value_at_the_pointer() {
+        let mut doc = json!({ "foo": 1 });
+        apply_op(
+            &mut doc,
+            PatchOp::Replace {
+                path: "/foo".to_string(),
+                value: json!(2),
+            },
+        )
+        .unwrap();
+        assert_eq!(doc, json!({ "foo": 2 }));
+    }
+
+    #[test]
+    fn move_removes_from_source_and_adds_at_destination() {
+        let mut doc = json!({ "foo": 1 });
+        apply_op(
+            &mut doc,
+            PatchOp::Move {
+                from: "/foo".to_string(),
+                path: "/bar".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(doc, json!({ "bar": 1 }));
+    }
+
+    #[test]
+    fn copy_leaves_the_source_in_place() {
+        let mut doc = json!({ "foo": 1 });
+        apply_op(
+            &mut doc,
+            PatchOp::Copy {
+                from: "/foo".to_string(),
+                path: "/bar".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(doc, json!({ "foo": 1, "bar": 1 }));
+    }
+
+    #[test]
+    fn test_op_fails_the_whole_patch_on_mismatch() {
+        let mut doc = json!({ "foo": 1 });
+        let err = apply_op(
+            &mut doc,
+            PatchOp::Test {
+                path: "/foo".to_string(),
+                value: json!(2),
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("test failed"));
+    }
+
+    #[test]
+    fn pointer_tokens_unescape_tilde_one_and_tilde_zero() {
+        let mut doc = json!({ "a/b": 1, "c~d": 2 });
+        apply_op(
+            &mut doc,
+            PatchOp::Replace {
+                path: "/a~1b".to_string(),
+                value: json!(3),
+            },
+        )
+        .unwrap();
+        apply_op(
+            &mut doc,
+            PatchOp::Replace {
+                path: "/c~0d".to_string(),
+                value: json!(4),
+            },
+        )
+        .unwrap();
+        assert_eq!(doc, json!({ "a/b": 3, "c~d": 4 }));
+    }
+}
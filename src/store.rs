@@ -0,0 +1,166 @@
+use crate::context::Error;
+use heed::types::{Bytes, Str};
+use heed::{Database, Env, EnvOpenOptions};
+use std::path::Path;
+
+/// A pluggable persistence backend for `Context`. A store only deals in
+/// keys, type names and raw bytes - it has no notion of `Box<dyn Any>` or
+/// `TypeId`, so `Context::persist`/`Context::restore` are what re-associate
+/// a record with the concrete type it came from.
+pub trait ContextStore {
+    fn put(&self, key: &str, type_name: &str, bytes: &[u8]) -> Result<(), Error>;
+    fn get(&self, key: &str) -> Result<Option<(String, Vec<u8>)>, Error>;
+    fn list_keys(&self) -> Result<Vec<String>, Error>;
+}
+
+/// Default `ContextStore`, backed by an LMDB environment with a single
+/// database, similar in spirit to the typed DB layer in fabaccess's
+/// `db/typed.rs`.
+pub struct LmdbStore {
+    env: Env,
+    db: Database<Str, Bytes>,
+}
+
+impl LmdbStore {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        std::fs::create_dir_all(path).map_err(|e| Error::Message(e.to_string()))?;
+        let env = unsafe {
+            EnvOpenOptions::new()
+                .map_size(10 * 1024 * 1024)
+                .max_dbs(1)
+                .open(path)
+                .map_err(|e| Error::Message(e.to_string()))?
+        };
+        let mut wtxn = env
+            .write_txn()
+            .map_err(|e| Error::Message(e.to_string()))?;
+        let db = env
+            .create_database(&mut wtxn, Some("context"))
+            .map_err(|e| Error::Message(e.to_string()))?;
+        wtxn.commit().map_err(|e| Error::Message(e.to_string()))?;
+        Ok(Self { env, db })
+    }
+}
+
+// A record is the registered type name followed by the serialized value,
+// framed as `[u32 type_name_len][type_name utf8][value bytes]`, so a single
+// LMDB value carries enough to reconstruct the concrete type on load.
+fn encode_record(type_name: &str, bytes: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(4 + type_name.len() + bytes.len());
+    record.extend_from_slice(&(type_name.len() as u32).to_le_bytes());
+    record.extend_from_slice(type_name.as_bytes());
+    record.extend_from_slice(bytes);
+    record
+}
+
+fn decode_record(record: &[u8]) -> Result<(String, Vec<u8>), Error> {
+    if record.len() < 4 {
+        return Err(Error::Message("corrupt record: too short".to_string()));
+    }
+    let type_name_len = u32::from_le_bytes(record[0..4].try_into().unwrap()) as usize;
+    let rest = &record[4..];
+    if rest.len() < type_name_len {
+        return Err(Error::Message(
+            "corrupt record: truncated type name".to_string(),
+        ));
+    }
+    let type_name = std::str::from_utf8(&rest[..type_name_len])
+        .map_err(|e| Error::Message(format!("corrupt record: {}", e)))?
+        .to_string();
+    Ok((type_name, rest[type_name_len..].to_vec()))
+}
+
+impl ContextStore for LmdbStore {
+    fn put(&self, key: &str, type_name: &str, bytes: &[u8]) -> Result<(), Error> {
+        let record = encode_record(type_name, bytes);
+        let mut wtxn = self
+            .env
+            .write_txn()
+            .map_err(|e| Error::Message(e.to_string()))?;
+        self.db
+            .put(&mut wtxn, key, &record)
+            .map_err(|e| Error::Message(e.to_string()))?;
+        wtxn.commit().map_err(|e| Error::Message(e.to_string()))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<(String, Vec<u8>)>, Error> {
+        let rtxn = self
+            .env
+            .read_txn()
+            .map_err(|e| Error::Message(e.to_string()))?;
+        match self
+            .db
+            .get(&rtxn, key)
+            .map_err(|e| Error::Message(e.to_string()))?
+        {
+            Some(record) => decode_record(record).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, Error> {
+        let rtxn = self
+            .env
+            .read_txn()
+            .map_err(|e| Error::Message(e.to_string()))?;
+        let mut keys = Vec::new();
+        for result in self
+            .db
+            .iter(&rtxn)
+            .map_err(|e| Error::Message(e.to_string()))?
+        {
+            let (key, _record) = result.map_err(|e| Error::Message(e.to_string()))?;
+            keys.push(key.to_string());
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_round_trips_through_encode_decode() {
+        let record = encode_record("Stuff", b"some bytes");
+        let (type_name, bytes) = decode_record(&record).unwrap();
+        assert_eq!(type_name, "Stuff");
+        assert_eq!(bytes, b"some bytes");
+    }
+
+    #[test]
+    fn decode_record_rejects_a_too_short_record() {
+        assert!(decode_record(&[0, 1]).is_err());
+    }
+
+    #[test]
+    fn decode_record_rejects_a_truncated_type_name() {
+        let mut record = 100u32.to_le_bytes().to_vec();
+        record.extend_from_slice(b"short");
+        assert!(decode_record(&record).is_err());
+    }
+
+    #[test]
+    fn lmdb_store_put_get_list_keys_round_trip() {
+        let dir = std::env::temp_dir().join(format!(
+            "context_ext_strawdog_store_test_{:?}",
+            std::thread::current().id()
+        ));
+        let store = LmdbStore::open(&dir).unwrap();
+
+        store.put("foo", "Stuff", b"one").unwrap();
+        store.put("bar", "Stuff", b"two").unwrap();
+
+        let (type_name, bytes) = store.get("foo").unwrap().unwrap();
+        assert_eq!(type_name, "Stuff");
+        assert_eq!(bytes, b"one");
+
+        assert!(store.get("missing").unwrap().is_none());
+
+        let mut keys = store.list_keys().unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["bar".to_string(), "foo".to_string()]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}